@@ -0,0 +1,378 @@
+//! Structured AT-command engine for the EC800K modem.
+//!
+//! Replaces the old `resp_str.contains("SEND OK")` style of polling with a
+//! real line-oriented digester (the same idea as the `atat` crate's digester
+//! used in several ublox drivers): RX bytes are accumulated into a buffer,
+//! split on `\r\n`, and each line is classified as an echo, a data line, a
+//! terminator (`OK` / `ERROR` / `+CME ERROR: <n>` / `+CMS ERROR: <n>` /
+//! `SEND OK` / `SEND FAIL`), the `> ` send prompt, or a URC. A command
+//! future resolves as soon as its terminator is seen (or times out); URCs
+//! seen along the way are forwarded to [`URC_CHANNEL`] instead of being
+//! lost, so other tasks can react to `+QIURC: "recv",..` /
+//! `"closed",..` / `"pdpdeact",..` without polling.
+
+use defmt::{warn, Format};
+use embassy_rp::peripherals::UART0;
+use embassy_rp::uart::BufferedUart;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::pubsub::PubSubChannel;
+use embassy_time::{Duration, Instant};
+use embedded_io_async::{Read, Write};
+use heapless::{String, Vec};
+
+/// Max data lines buffered for a single command response.
+const MAX_RESPONSE_LINES: usize = 8;
+/// Max length of a single line (command responses, URCs).
+const LINE_CAP: usize = 256;
+/// Size of the raw ingest buffer backing the line framer.
+const LINE_BUF_CAP: usize = 1024;
+
+/// Broadcast channel of unsolicited result codes, fed from inside
+/// [`AtEngine`] whenever a `+QIURC:` (or similar) line shows up while
+/// waiting on a command or an open read. A `PubSubChannel` rather than a
+/// plain `Channel` because more than one task cares: the MQTT client
+/// watches its own link id, the supervisor watches for the HTTP link
+/// closing or the PDP context dropping - a single-consumer channel would
+/// have them stealing each other's URCs. Each subscriber gets every
+/// message; a message nobody has room for yet just evicts the oldest one
+/// for that subscriber rather than stalling the modem task.
+pub static URC_CHANNEL: PubSubChannel<CriticalSectionRawMutex, Urc, 8, 2, 1> = PubSubChannel::new();
+
+/// A `URC_CHANNEL` subscriber, named so callers (the MQTT client, the
+/// supervisor) don't each have to spell out the channel's generic params.
+pub type UrcSubscriber = embassy_sync::pubsub::Subscriber<'static, CriticalSectionRawMutex, Urc, 8, 2, 1>;
+
+#[derive(Debug, Format, Clone, Copy)]
+pub enum AtError {
+    /// No terminator / prefix arrived before the deadline.
+    Timeout,
+    /// UART read or write failed.
+    Io,
+    /// The modem returned `ERROR`.
+    Error,
+    /// The modem returned `+CME ERROR: <n>`.
+    CmeError(u16),
+    /// The modem returned `+CMS ERROR: <n>`.
+    CmsError(u16),
+    /// The modem returned `SEND FAIL` for a `QISEND`/`QSSLSEND`.
+    SendFail,
+    /// The modem returned `NO CARRIER`/`NO ANSWER` for an `ATD` dial.
+    NoCarrier,
+    /// The line framer's ingest buffer filled up without finding a
+    /// terminator; the buffer was discarded so the engine can keep going.
+    BufferFull,
+}
+
+/// An unsolicited result code: a line the modem emits on its own rather
+/// than in direct response to a command.
+#[derive(Debug, Format, Clone)]
+pub enum Urc {
+    /// `+QIURC: "recv",<link_id>` - data is waiting on a socket.
+    IncomingData { link_id: u8 },
+    /// `+QIURC: "closed",<link_id>` - the peer (or modem) closed the link.
+    Closed { link_id: u8 },
+    /// `+QIURC: "pdpdeact",<ctx_id>` - the PDP context was torn down.
+    PdpDeactivated { ctx_id: u8 },
+    /// Any other `+QIURC:` payload we don't special-case, kept verbatim.
+    Other(String<LINE_CAP>),
+}
+
+enum Terminator {
+    Ok,
+    Error,
+    CmeError(u16),
+    CmsError(u16),
+    SendOk,
+    SendFail,
+    /// `CONNECT` - the modem has dropped into PPP data mode after `ATD`.
+    Connect,
+    /// `NO CARRIER` - the `ATD` dial attempt didn't get a data-mode answer.
+    NoCarrier,
+    /// `NO ANSWER` - the `ATD` dial attempt wasn't picked up.
+    NoAnswer,
+}
+
+enum Classified {
+    Echo,
+    Blank,
+    Prompt,
+    Data(String<LINE_CAP>),
+    Terminator(Terminator),
+    Urc(Urc),
+}
+
+/// A completed command response: the data lines collected between the echo
+/// and the terminator (if any).
+pub struct Response {
+    pub lines: Vec<String<LINE_CAP>, MAX_RESPONSE_LINES>,
+}
+
+impl Response {
+    /// The first collected data line, if any - convenient for commands that
+    /// only ever return a single information line (`AT+CREG?`, `AT+CSQ`, ...).
+    pub fn first(&self) -> Option<&str> {
+        self.lines.first().map(|s| s.as_str())
+    }
+
+    /// Whether any collected line contains `needle` - handy for commands
+    /// whose single-line reply is the whole answer (`+QIACT: 1,...`).
+    pub fn contains(&self, needle: &str) -> bool {
+        self.lines.iter().any(|l| l.as_str().contains(needle))
+    }
+}
+
+/// Owns the UART and turns raw bytes into AT command responses and URCs.
+///
+/// This is the only thing in the firmware that talks to `BufferedUart`
+/// directly for AT traffic; everything else goes through `send`,
+/// `wait_for_prefix`, or (for the direct-push data phase of a socket)
+/// `read_raw` / `write_raw`.
+pub struct AtEngine {
+    uart: BufferedUart<'static, UART0>,
+    buf: Vec<u8, LINE_BUF_CAP>,
+}
+
+impl AtEngine {
+    pub fn new(uart: BufferedUart<'static, UART0>) -> Self {
+        Self {
+            uart,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Reclaim the raw UART, discarding any buffered-but-unconsumed bytes.
+    /// Used when something else needs to own the port outright, e.g.
+    /// handing it to the PPP runner once the modem is in data mode.
+    pub fn into_uart(self) -> BufferedUart<'static, UART0> {
+        self.uart
+    }
+
+    /// Send `cmd` (without the trailing `\r\n`) and wait up to `timeout`
+    /// for a terminator, collecting any data lines seen in between.
+    pub async fn send_timeout(&mut self, cmd: &str, timeout: Duration) -> Result<Response, AtError> {
+        let mut line = String::<LINE_CAP>::new();
+        let _ = line.push_str(cmd);
+        let _ = line.push_str("\r\n");
+        self.uart
+            .write_all(line.as_bytes())
+            .await
+            .map_err(|_| AtError::Io)?;
+        self.await_terminator(timeout).await
+    }
+
+    /// `send_timeout` with the engine's default 5s timeout.
+    pub async fn send(&mut self, cmd: &str) -> Result<Response, AtError> {
+        self.send_timeout(cmd, Duration::from_secs(5)).await
+    }
+
+    /// Wait for a line to show up, without sending anything first. Used
+    /// after a command whose `OK` only means "accepted" and whose real
+    /// result arrives later on its own, e.g. `+QIOPEN: <id>,<err>` some
+    /// time after `AT+QIOPEN`'s immediate `OK`. URCs seen while waiting
+    /// are still forwarded to [`URC_CHANNEL`].
+    pub async fn wait_for_prefix(
+        &mut self,
+        prefix: &str,
+        timeout: Duration,
+    ) -> Result<String<LINE_CAP>, AtError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let raw = self.next_line_before(deadline).await?;
+            if raw.starts_with(prefix) {
+                return Ok(raw);
+            }
+            if let Classified::Urc(urc) = classify(&raw) {
+                URC_CHANNEL.publish_immediate(urc);
+            }
+        }
+    }
+
+    /// Write raw (non-AT) bytes straight to the UART, e.g. the HTTP request
+    /// body once `AT+QISEND`'s `> ` prompt has been seen.
+    pub async fn write_raw(&mut self, bytes: &[u8]) -> Result<(), AtError> {
+        self.uart.write_all(bytes).await.map_err(|_| AtError::Io)
+    }
+
+    /// Wait for the `SEND OK` / `SEND FAIL` terminator that follows writing
+    /// a `QISEND`/`QSSLSEND` payload with `write_raw`.
+    pub async fn await_send_result(&mut self, timeout: Duration) -> Result<(), AtError> {
+        self.await_terminator(timeout).await.map(|_| ())
+    }
+
+    /// Wait for the plain `OK`/`ERROR` terminator following data that was
+    /// consumed directly with `read_raw` rather than through the line
+    /// framer - e.g. the raw payload bytes of an `AT+QIRD` response, which
+    /// can contain `\r\n` as ordinary payload bytes and would desync the
+    /// framer if read as text. Same underlying wait as `await_send_result`,
+    /// just named for that use.
+    pub async fn await_ok(&mut self, timeout: Duration) -> Result<(), AtError> {
+        self.await_terminator(timeout).await.map(|_| ())
+    }
+
+    /// Read raw (non-AT) bytes, e.g. the direct-push body of a socket
+    /// opened in access mode 1. Drains anything already pulled into the
+    /// line buffer before issuing a fresh UART read, so data that arrived
+    /// hot on the heels of `SEND OK` isn't lost to the line framer.
+    pub async fn read_raw(&mut self, out: &mut [u8], timeout: Duration) -> Result<usize, AtError> {
+        if !self.buf.is_empty() {
+            let n = self.buf.len().min(out.len());
+            out[..n].copy_from_slice(&self.buf[..n]);
+            let remainder: Vec<u8, LINE_BUF_CAP> =
+                Vec::from_slice(&self.buf[n..]).unwrap_or_default();
+            self.buf = remainder;
+            return Ok(n);
+        }
+        match embassy_time::with_timeout(timeout, self.uart.read(out)).await {
+            Ok(Ok(n)) => Ok(n),
+            Ok(Err(_)) => Err(AtError::Io),
+            Err(_) => Err(AtError::Timeout),
+        }
+    }
+
+    /// Drive the line framer until a terminator shows up, forwarding any
+    /// URCs seen along the way instead of treating them as the answer.
+    async fn await_terminator(&mut self, timeout: Duration) -> Result<Response, AtError> {
+        let deadline = Instant::now() + timeout;
+        let mut lines = Vec::new();
+        loop {
+            let raw = self.next_line_before(deadline).await?;
+            match classify(&raw) {
+                Classified::Echo | Classified::Blank => continue,
+                Classified::Prompt => return Ok(Response { lines }),
+                Classified::Urc(urc) => {
+                    URC_CHANNEL.publish_immediate(urc);
+                }
+                Classified::Terminator(Terminator::Ok) => return Ok(Response { lines }),
+                Classified::Terminator(Terminator::SendOk) => return Ok(Response { lines }),
+                Classified::Terminator(Terminator::Connect) => return Ok(Response { lines }),
+                Classified::Terminator(Terminator::Error) => return Err(AtError::Error),
+                Classified::Terminator(Terminator::SendFail) => return Err(AtError::SendFail),
+                Classified::Terminator(Terminator::NoCarrier) => return Err(AtError::NoCarrier),
+                Classified::Terminator(Terminator::NoAnswer) => return Err(AtError::NoCarrier),
+                Classified::Terminator(Terminator::CmeError(n)) => return Err(AtError::CmeError(n)),
+                Classified::Terminator(Terminator::CmsError(n)) => return Err(AtError::CmsError(n)),
+                Classified::Data(s) => {
+                    // Extra lines beyond MAX_RESPONSE_LINES are dropped
+                    // rather than failing the whole command.
+                    let _ = lines.push(s);
+                }
+            }
+        }
+    }
+
+    async fn next_line_before(&mut self, deadline: Instant) -> Result<String<LINE_CAP>, AtError> {
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining == Duration::from_ticks(0) {
+                return Err(AtError::Timeout);
+            }
+            if let Some(line) = self.take_buffered_line() {
+                return Ok(line);
+            }
+            let mut chunk = [0u8; 128];
+            let n = match embassy_time::with_timeout(remaining, self.uart.read(&mut chunk)).await {
+                Ok(Ok(n)) => n,
+                Ok(Err(_)) => return Err(AtError::Io),
+                Err(_) => return Err(AtError::Timeout),
+            };
+            if self.buf.extend_from_slice(&chunk[..n]).is_err() {
+                warn!("AT line buffer overflow, discarding");
+                self.buf.clear();
+                return Err(AtError::BufferFull);
+            }
+        }
+    }
+
+    /// Pull one line out of the accumulated byte buffer, if one is
+    /// complete. The `> ` send prompt has no trailing CRLF, so it's
+    /// special-cased rather than treated as a partial line.
+    fn take_buffered_line(&mut self) -> Option<String<LINE_CAP>> {
+        if let Some(pos) = self.buf.windows(2).position(|w| w == b"\r\n") {
+            let mut line = String::new();
+            if let Ok(s) = core::str::from_utf8(&self.buf[..pos]) {
+                let _ = line.push_str(s);
+            }
+            let remainder: Vec<u8, LINE_BUF_CAP> =
+                Vec::from_slice(&self.buf[pos + 2..]).unwrap_or_default();
+            self.buf = remainder;
+            return Some(line);
+        }
+        if self.buf.ends_with(b"> ") {
+            self.buf.clear();
+            let mut s = String::new();
+            let _ = s.push_str(">");
+            return Some(s);
+        }
+        None
+    }
+}
+
+fn classify(line: &str) -> Classified {
+    let trimmed = line.trim();
+    if trimmed.is_empty() {
+        return Classified::Blank;
+    }
+    if trimmed == ">" {
+        return Classified::Prompt;
+    }
+    if trimmed == "OK" {
+        return Classified::Terminator(Terminator::Ok);
+    }
+    if trimmed == "ERROR" {
+        return Classified::Terminator(Terminator::Error);
+    }
+    if trimmed == "SEND OK" {
+        return Classified::Terminator(Terminator::SendOk);
+    }
+    if trimmed == "SEND FAIL" {
+        return Classified::Terminator(Terminator::SendFail);
+    }
+    if trimmed == "CONNECT" || trimmed.starts_with("CONNECT ") {
+        return Classified::Terminator(Terminator::Connect);
+    }
+    if trimmed == "NO CARRIER" {
+        return Classified::Terminator(Terminator::NoCarrier);
+    }
+    if trimmed == "NO ANSWER" {
+        return Classified::Terminator(Terminator::NoAnswer);
+    }
+    if let Some(rest) = trimmed.strip_prefix("+CME ERROR: ") {
+        return Classified::Terminator(Terminator::CmeError(rest.trim().parse().unwrap_or(0)));
+    }
+    if let Some(rest) = trimmed.strip_prefix("+CMS ERROR: ") {
+        return Classified::Terminator(Terminator::CmsError(rest.trim().parse().unwrap_or(0)));
+    }
+    if trimmed.starts_with("AT+") || trimmed.starts_with("AT\r") || trimmed == "AT" {
+        return Classified::Echo;
+    }
+    if trimmed.starts_with("+QIURC:") {
+        if let Some(urc) = parse_urc(trimmed) {
+            return Classified::Urc(urc);
+        }
+    }
+    let mut s = String::new();
+    let _ = s.push_str(trimmed);
+    Classified::Data(s)
+}
+
+fn parse_urc(line: &str) -> Option<Urc> {
+    let rest = line.strip_prefix("+QIURC: ")?;
+    if let Some(rest) = rest.strip_prefix("\"recv\",") {
+        return Some(Urc::IncomingData {
+            link_id: rest.trim().parse().unwrap_or(0),
+        });
+    }
+    if let Some(rest) = rest.strip_prefix("\"closed\",") {
+        return Some(Urc::Closed {
+            link_id: rest.trim().parse().unwrap_or(0),
+        });
+    }
+    if let Some(rest) = rest.strip_prefix("\"pdpdeact\",") {
+        return Some(Urc::PdpDeactivated {
+            ctx_id: rest.trim().parse().unwrap_or(0),
+        });
+    }
+    let mut s = String::new();
+    let _ = s.push_str(line);
+    Some(Urc::Other(s))
+}
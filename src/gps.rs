@@ -0,0 +1,165 @@
+//! GNSS/GPS positioning via the EC800K's onboard GNSS engine.
+//!
+//! Powers on GNSS (`AT+QGPS=1`) and periodically polls `AT+QGPSLOC?`,
+//! parsing the fix into a typed [`GpsFix`] and publishing it for the HTTP
+//! server's `/gps` route. `AT+QGPSLOC?` returning `+CME ERROR: 516` means
+//! "no fix yet" - GNSS needs open sky and can take minutes to lock, so
+//! that's treated as a normal pending status rather than a failure.
+
+use defmt::{info, warn};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_time::{Duration, Timer};
+use heapless::String;
+
+use crate::at::{AtEngine, AtError};
+use crate::mqtt::SharedAtEngine;
+use crate::supervisor;
+
+/// How long `gnss_task` waits between checks while the modem isn't `Ready`
+/// yet, and between retries of a failed `AT+QGPS=1`.
+const POWER_ON_RETRY: Duration = Duration::from_secs(5);
+
+/// `AT+CME ERROR` code `AT+QGPSLOC?` returns while GNSS hasn't fixed yet.
+const CME_NO_FIX: u16 = 516;
+
+/// How often `gnss_task` polls `AT+QGPSLOC?` once GNSS is powered on.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Depth-1 "latest value" channel: the `/gps` route only ever wants the
+/// most recent status, not a backlog, so `current()` always hands the
+/// value straight back after reading it.
+static LATEST_FIX: Channel<CriticalSectionRawMutex, GpsStatus, 1> = Channel::new();
+
+#[derive(Clone)]
+pub enum GpsStatus {
+    /// GNSS hasn't been powered on yet - either the modem isn't `Ready` to
+    /// take the command, or no `AT+QGPS=1` attempt has succeeded so far.
+    /// Distinct from `Pending` so `/gps` can tell "never enabled" apart
+    /// from "on, but no fix yet".
+    Off,
+    /// GNSS is on but hasn't produced a fix yet.
+    Pending,
+    Fix(GpsFix),
+}
+
+#[derive(Clone)]
+pub struct GpsFix {
+    pub latitude: f32,
+    pub longitude: f32,
+    pub hdop: f32,
+    pub altitude: f32,
+    pub fix_quality: u8,
+    pub utc_time: String<16>,
+    pub satellites: u8,
+}
+
+/// The most recently published GNSS status, defaulting to `Off` if
+/// `gnss_task` hasn't powered GNSS on yet.
+pub async fn current() -> GpsStatus {
+    match LATEST_FIX.try_receive() {
+        Ok(status) => {
+            let _ = LATEST_FIX.try_send(status.clone());
+            status
+        }
+        Err(_) => GpsStatus::Off,
+    }
+}
+
+async fn publish(status: GpsStatus) {
+    let _ = LATEST_FIX.try_receive();
+    let _ = LATEST_FIX.try_send(status);
+}
+
+/// Power on GNSS and poll it for fixes for as long as the firmware runs.
+/// Shares the modem with the HTTP proxy and MQTT client through the same
+/// engine mutex, so this coexists with an active data session rather than
+/// needing its own socket. Waits for `supervisor::current_state()` to reach
+/// `Ready` before touching the engine, same as `uart_task` - this task is
+/// spawned at the same time as `supervisor_task`, so without that gate it
+/// can race `AT+QGPS=1` in before the modem has even answered a plain `AT`.
+#[embassy_executor::task]
+pub async fn gnss_task(engine: &'static SharedAtEngine) {
+    loop {
+        if supervisor::current_state().await != supervisor::ModemState::Ready {
+            Timer::after(POWER_ON_RETRY).await;
+            continue;
+        }
+
+        let mut eng = engine.lock().await;
+        match eng.send("AT+QGPS=1").await {
+            Ok(_) => {
+                info!("GNSS powered on");
+                break;
+            }
+            Err(AtError::CmeError(n)) if n == 501 => {
+                // 501 = "already active" - fine, someone (a warm reset)
+                // already turned it on.
+                info!("GNSS already powered on");
+                break;
+            }
+            Err(e) => warn!("AT+QGPS=1 failed: {:?}, retrying", e),
+        }
+        drop(eng);
+        Timer::after(POWER_ON_RETRY).await;
+    }
+
+    publish(GpsStatus::Pending).await;
+
+    loop {
+        let status = {
+            let mut eng = engine.lock().await;
+            query_location(&mut eng).await
+        };
+        publish(status).await;
+        Timer::after(POLL_INTERVAL).await;
+    }
+}
+
+async fn query_location(engine: &mut AtEngine) -> GpsStatus {
+    match engine.send_timeout("AT+QGPSLOC?", Duration::from_secs(5)).await {
+        Ok(resp) => resp
+            .lines
+            .iter()
+            .find_map(|l| parse_fix(l.as_str()))
+            .map(GpsStatus::Fix)
+            .unwrap_or(GpsStatus::Pending),
+        Err(AtError::CmeError(n)) if n == CME_NO_FIX => GpsStatus::Pending,
+        Err(e) => {
+            warn!("AT+QGPSLOC? failed: {:?}", e);
+            GpsStatus::Pending
+        }
+    }
+}
+
+/// Parse a `+QGPSLOC: <utc>,<lat>,<lon>,<hdop>,<alt>,<fix>,<cog>,<spkm>,
+/// <spkn>,<date>,<nsat>` line.
+fn parse_fix(line: &str) -> Option<GpsFix> {
+    let rest = line.strip_prefix("+QGPSLOC: ")?;
+    let mut fields = rest.split(',');
+
+    let utc = fields.next()?;
+    let latitude: f32 = fields.next()?.parse().ok()?;
+    let longitude: f32 = fields.next()?.parse().ok()?;
+    let hdop: f32 = fields.next()?.parse().ok()?;
+    let altitude: f32 = fields.next()?.parse().ok()?;
+    let fix_quality: u8 = fields.next()?.parse().ok()?;
+    let _cog = fields.next()?;
+    let _speed_kmh = fields.next()?;
+    let _speed_kn = fields.next()?;
+    let _date = fields.next()?;
+    let satellites: u8 = fields.next()?.trim().parse().ok()?;
+
+    let mut utc_time = String::new();
+    utc_time.push_str(utc).ok()?;
+
+    Some(GpsFix {
+        latitude,
+        longitude,
+        hdop,
+        altitude,
+        fix_quality,
+        utc_time,
+        satellites,
+    })
+}
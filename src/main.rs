@@ -1,6 +1,12 @@
 #![no_std]
 #![no_main]
 
+mod at;
+mod gps;
+mod mqtt;
+mod ppp;
+mod supervisor;
+
 use core::fmt::Write as _;
 use cyw43_pio::{PioSpi, RM2_CLOCK_DIVIDER};
 use defmt::*;
@@ -12,13 +18,39 @@ use embassy_rp::gpio::{Level, Output};
 use embassy_rp::peripherals::{DMA_CH0, PIO0, UART0};
 use embassy_rp::pio::{InterruptHandler as PioInterruptHandler, Pio};
 use embassy_rp::uart::{BufferedInterruptHandler, BufferedUart, Config as UartConfig};
+use embassy_futures::select::{select, Either};
+use embassy_sync::mutex::Mutex;
+use embassy_sync::pipe::Pipe;
+use embassy_sync::signal::Signal;
 use embassy_time::{Duration, Timer};
 use embedded_io_async::Read;
 use embedded_io_async::Write;
-use heapless::String;
+use heapless::{String, Vec};
 use static_cell::StaticCell;
 use {defmt_rtt as _, panic_probe as _};
 
+use at::AtEngine;
+use mqtt::{MqttConfig, SharedAtEngine};
+use ppp::{PppDevice, PppState, USE_PPP_TRANSPORT};
+
+/// Flip this to spawn `mqtt_task` - off by default since `MQTT_CONFIG`
+/// below is a placeholder, and the task would otherwise loop forever
+/// every 5s trying (and failing) to reach `broker.example.com`, contending
+/// for `SharedAtEngine` with the HTTP proxy and GNSS on each attempt.
+/// Consistent with how `ppp::USE_PPP_TRANSPORT` gates the PPP transport.
+const ENABLE_MQTT: bool = false;
+
+/// MQTT broker this device reports telemetry to and takes commands from.
+/// Set to a real broker/credentials before flipping `ENABLE_MQTT` to `true`.
+const MQTT_CONFIG: MqttConfig = MqttConfig {
+    host: "broker.example.com",
+    port: 1883,
+    client_id: "pico-lte-proxy",
+    username: None,
+    password: None,
+    keepalive_secs: 60,
+};
+
 // Program metadata
 #[link_section = ".bi_entries"]
 #[used]
@@ -56,6 +88,11 @@ async fn net_task(mut runner: embassy_net::Runner<'static, cyw43::NetDriver<'sta
     runner.run().await
 }
 
+#[embassy_executor::task]
+async fn ppp_net_task(mut runner: embassy_net::Runner<'static, PppDevice<'static>>) -> ! {
+    runner.run().await
+}
+
 // Global channel for UART communication
 static UART_CHANNEL: embassy_sync::channel::Channel<
     embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
@@ -65,56 +102,90 @@ static UART_CHANNEL: embassy_sync::channel::Channel<
 
 static UART_RESPONSE: embassy_sync::channel::Channel<
     embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
-    UartResponse,
+    FetchOutcome,
     1,
 > = embassy_sync::channel::Channel::new();
 
+/// Max size of the header block `fetch_via_lte` will buffer while looking
+/// for the `\r\n\r\n` that ends it - generous for any real HTTP response
+/// header, tiny next to the old whole-body `String<8192>`.
+const HEADER_SCAN_CAP: usize = 1024;
+
+/// Header-stripped response body bytes, pumped from the UART task straight
+/// to whichever client socket is waiting in `http_server_task`. Fixed,
+/// small capacity regardless of how large the upstream response is -
+/// `fetch_via_lte`/`fetch_via_ssl` block on writing to this once it fills
+/// up, which throttles the modem read loop to the client's drain rate
+/// instead of buffering the whole response in RAM.
+static RESPONSE_BODY: Pipe<embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex, 2048> =
+    Pipe::new();
+
+/// Signalled once the UART task has written the last body byte (or given
+/// up) for the in-flight request, so `http_server_task` knows when to emit
+/// the terminating `0\r\n\r\n` chunk instead of waiting for more data that
+/// isn't coming.
+static FETCH_DONE: Signal<embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex, ()> =
+    Signal::new();
+
+/// `http://` vs `https://` target, so the UART task knows whether to drive
+/// the plain `AT+QIOPEN` socket family or the modem's SSL context
+/// (`AT+QSSLOPEN`/`QSSLSEND`/`QSSLRECV`/`QSSLCLOSE`).
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Scheme {
+    Http,
+    Https,
+}
+
+impl Scheme {
+    fn default_port(self) -> u16 {
+        match self {
+            Scheme::Http => 80,
+            Scheme::Https => 443,
+        }
+    }
+}
+
 #[derive(Clone)]
 struct UartRequest {
+    scheme: Scheme,
     host: String<64>,
     path: String<128>,
 }
 
-struct UartResponse {
+/// Outcome handed back over `UART_RESPONSE` as soon as it's known. A
+/// failure is the final word; a success just means "the upstream response
+/// headers are parsed, start writing the chunked reply" - the body itself
+/// streams separately through `RESPONSE_BODY` and finishes with
+/// `FETCH_DONE`.
+enum FetchOutcome {
+    Streaming,
+    Failed(String<128>),
+}
+
+async fn send_failed(msg: &str) {
+    let mut data = String::<128>::new();
+    let _ = data.push_str(msg);
+    UART_RESPONSE.send(FetchOutcome::Failed(data)).await;
+}
+
+/// Buffered response from the PPP transport, which still reads its whole
+/// body into RAM before replying - only `fetch_via_lte`/`fetch_via_ssl`
+/// (the `AT+QIOPEN` transport) stream through `RESPONSE_BODY`.
+struct PppResponse {
     data: String<8192>,
     success: bool,
 }
 
+/// Waits for HTTP requests and runs them against the modem. Bringing the
+/// modem up (and keeping it up) is `supervisor::supervisor_task`'s job, not
+/// this task's - a request that arrives before the modem is `Ready` (or
+/// while the supervisor is re-attaching after link trouble) gets a clean
+/// error back rather than this task blocking on a modem that isn't there
+/// yet.
 #[embassy_executor::task]
-async fn uart_task(mut uart: BufferedUart<'static, UART0>) {
+async fn uart_task(engine: &'static SharedAtEngine) {
     info!("UART task started at {} baud", UART_BAUDRATE);
 
-    // Initialize EC800K
-    Timer::after(Duration::from_secs(2)).await;
-
-    info!("Initializing EC800K...");
-    send_at_command(&mut uart, "AT").await;
-    Timer::after(Duration::from_millis(500)).await;
-
-    send_at_command(&mut uart, "AT+CPIN?").await;
-    Timer::after(Duration::from_millis(500)).await;
-
-    send_at_command(&mut uart, "AT+CREG?").await;
-    Timer::after(Duration::from_millis(500)).await;
-
-    send_at_command(&mut uart, "AT+CGATT=1").await;
-    Timer::after(Duration::from_secs(1)).await;
-
-    send_at_command(&mut uart, "AT+QICSGP=1,1,\"CTNET\"").await;
-    Timer::after(Duration::from_millis(500)).await;
-
-    send_at_command(&mut uart, "AT+QIACT=1").await;
-    Timer::after(Duration::from_secs(2)).await;
-
-    send_at_command(&mut uart, "AT+QIACT?").await;
-    Timer::after(Duration::from_millis(500)).await;
-
-    send_at_command(&mut uart, "AT+QIDNSCFG=1,\"114.114.114.114\",\"8.8.8.8\"").await;
-    Timer::after(Duration::from_millis(500)).await;
-
-    info!("EC800K initialized successfully!");
-
-    // Main loop - wait for HTTP requests
     loop {
         let request = UART_CHANNEL.receive().await;
         info!(
@@ -123,92 +194,63 @@ async fn uart_task(mut uart: BufferedUart<'static, UART0>) {
             request.path.as_str()
         );
 
-        let result = fetch_via_lte(&mut uart, &request.host, &request.path).await;
-
-        UART_RESPONSE.send(result).await;
-    }
-}
-
-async fn send_at_command(uart: &mut BufferedUart<'static, UART0>, cmd: &str) {
-    let mut cmd_buf = String::<256>::new();
-    let _ = cmd_buf.push_str(cmd);
-    let _ = cmd_buf.push_str("\r\n");
-
-    info!("TX: {}", cmd);
-    let _ = uart.write_all(cmd_buf.as_bytes()).await;
-
-    // Read response
-    let mut response = [0u8; 512];
-    Timer::after(Duration::from_millis(100)).await;
+        if supervisor::current_state().await != supervisor::ModemState::Ready {
+            warn!("Modem not ready yet, rejecting request");
+            send_failed("Modem is still attaching to the network, try again shortly").await;
+            continue;
+        }
 
-    if let Ok(n) =
-        embassy_time::with_timeout(Duration::from_secs(2), uart.read(&mut response)).await
-    {
-        if let Ok(n) = n {
-            if let Ok(resp_str) = core::str::from_utf8(&response[..n]) {
-                info!("RX: {}", resp_str.trim());
-            }
+        match request.scheme {
+            Scheme::Http => fetch_via_lte(engine, &request.host, &request.path).await,
+            Scheme::Https => fetch_via_ssl(engine, &request.host, &request.path).await,
         }
     }
 }
 
-async fn clear_uart_buffer(uart: &mut BufferedUart<'static, UART0>) {
-    Timer::after(Duration::from_millis(500)).await;
-    let mut discard = [0u8; 256];
-    while let Ok(_) =
-        embassy_time::with_timeout(Duration::from_millis(100), uart.read(&mut discard)).await
-    {}
-}
-
-async fn fetch_via_lte(
-    uart: &mut BufferedUart<'static, UART0>,
-    host: &str,
-    path: &str,
-) -> UartResponse {
+/// Fetch `path` from `host` over the modem's plain `AT+QIOPEN` socket and
+/// stream the response body straight through `RESPONSE_BODY` as it arrives,
+/// rather than buffering it - `http_server_task` is relaying it onward with
+/// `Transfer-Encoding: chunked`, so it never needs the whole thing at once.
+async fn fetch_via_lte(engine: &'static SharedAtEngine, host: &str, path: &str) {
     info!("Fetching http://{}{} via LTE...", host, path);
+    let mut engine = engine.lock().await;
 
-    // Clear buffer
-    clear_uart_buffer(uart).await;
-
-    // Step 1: Open TCP connection
+    // Step 1: Open TCP connection. The command's own OK just means
+    // "accepted"; the real connect result arrives later as its own line.
     info!("1. Opening TCP connection...");
     let mut open_cmd = String::<256>::new();
-    let _ = write!(open_cmd, "AT+QIOPEN=1,0,\"TCP\",\"{}\",80,0,1\r\n", host);
-    let _ = uart.write_all(open_cmd.as_bytes()).await;
-
-    // Wait for +QIOPEN: 0,0
-    let mut response = [0u8; 256];
-    let mut connected = false;
-    for _ in 0..20 {
-        Timer::after(Duration::from_millis(500)).await;
-        if let Ok(n) = embassy_time::with_timeout(
-            Duration::from_millis(500),
-            uart.read(&mut response),
-        )
+    let _ = write!(open_cmd, "AT+QIOPEN=1,0,\"TCP\",\"{}\",80,0,1", host);
+    if let Err(e) = engine.send_timeout(&open_cmd, Duration::from_secs(5)).await {
+        warn!("QIOPEN rejected: {:?}", e);
+        if matches!(e, at::AtError::Timeout) {
+            supervisor::request_reattach();
+        }
+        send_failed("TCP connection failed").await;
+        return;
+    }
+
+    let open_result = match engine
+        .wait_for_prefix("+QIOPEN:", Duration::from_secs(30))
         .await
-        {
-            if let Ok(n) = n {
-                if let Ok(resp_str) = core::str::from_utf8(&response[..n]) {
-                    info!("Open response: {}", resp_str);
-                    if resp_str.contains("+QIOPEN: 0,0") {
-                        connected = true;
-                        break;
-                    }
-                }
+    {
+        Ok(line) => line,
+        Err(e) => {
+            warn!("QIOPEN result timed out: {:?}", e);
+            if matches!(e, at::AtError::Timeout) {
+                supervisor::request_reattach();
             }
+            send_failed("TCP connection failed").await;
+            return;
         }
-    }
+    };
 
-    if !connected {
-        warn!("TCP connection failed");
-        return UartResponse {
-            data: String::from("TCP connection failed"),
-            success: false,
-        };
+    if !open_result.contains("+QIOPEN: 0,0") {
+        warn!("TCP connection failed: {}", open_result.as_str());
+        send_failed("TCP connection failed").await;
+        return;
     }
 
     info!("✅ TCP connected");
-    Timer::after(Duration::from_secs(1)).await;
 
     // Step 2: Prepare HTTP request
     let mut http_request = String::<512>::new();
@@ -218,114 +260,352 @@ async fn fetch_via_lte(
         path, host
     );
 
-    // Step 3: Send HTTP data
+    // Step 3: Send HTTP data - wait for the '> ' prompt, then write the
+    // request body raw and wait for the SEND OK/SEND FAIL terminator.
     info!("2. Sending HTTP request...");
     let mut send_cmd = String::<64>::new();
-    let _ = write!(send_cmd, "AT+QISEND=0,{}\r\n", http_request.len());
-    let _ = uart.write_all(send_cmd.as_bytes()).await;
-
-    // Wait for '>'
-    Timer::after(Duration::from_millis(500)).await;
-    let mut got_prompt = false;
-    if let Ok(n) =
-        embassy_time::with_timeout(Duration::from_secs(5), uart.read(&mut response)).await
-    {
-        if let Ok(n) = n {
-            if let Ok(resp_str) = core::str::from_utf8(&response[..n]) {
-                if resp_str.contains(">") {
-                    got_prompt = true;
-                }
-            }
-        }
+    let _ = write!(send_cmd, "AT+QISEND=0,{}", http_request.len());
+    if let Err(e) = engine.send_timeout(&send_cmd, Duration::from_secs(5)).await {
+        warn!("No send prompt received: {:?}", e);
+        let _ = engine.send("AT+QICLOSE=0").await;
+        send_failed("No send prompt").await;
+        return;
     }
 
-    if !got_prompt {
-        warn!("No send prompt received");
-        let _ = uart.write_all(b"AT+QICLOSE=0\r\n").await;
-        return UartResponse {
-            data: String::from("No send prompt"),
-            success: false,
-        };
+    if let Err(e) = engine.write_raw(http_request.as_bytes()).await {
+        warn!("Failed to write HTTP request: {:?}", e);
+        let _ = engine.send("AT+QICLOSE=0").await;
+        send_failed("Write failed").await;
+        return;
     }
 
-    // Send actual HTTP data
-    let _ = uart.write_all(http_request.as_bytes()).await;
-    Timer::after(Duration::from_millis(500)).await;
-
-    // Wait for SEND OK
     info!("3. Waiting for SEND OK...");
-    let mut got_send_ok = false;
-    for _ in 0..10 {
-        if let Ok(n) = embassy_time::with_timeout(
-            Duration::from_millis(500),
-            uart.read(&mut response),
-        )
-        .await
+    match engine.await_send_result(Duration::from_secs(5)).await {
+        Ok(()) => info!("✅ SEND OK received"),
+        Err(e) => warn!("SEND OK not received: {:?}", e),
+    }
+
+    // Step 4: stream the response. The socket was opened in direct-push
+    // mode, so bytes arrive raw rather than as AT syntax. Bytes are
+    // buffered only until the `\r\n\r\n` that ends the upstream response's
+    // own headers is found (`HEADER_SCAN_CAP` bounds that); everything
+    // after goes straight into `RESPONSE_BODY`.
+    info!("4. Streaming HTTP response...");
+    let mut header_buf: Vec<u8, HEADER_SCAN_CAP> = Vec::new();
+    let mut read_buf = [0u8; 512];
+    let mut no_data_count = 0;
+    let mut headers_done = false;
+    let mut body_len = 0usize;
+
+    for _ in 0..60 {
+        // 30 seconds max
+        match engine
+            .read_raw(&mut read_buf, Duration::from_millis(500))
+            .await
         {
-            if let Ok(n) = n {
-                if let Ok(resp_str) = core::str::from_utf8(&response[..n]) {
-                    if resp_str.contains("SEND OK") {
-                        got_send_ok = true;
-                        info!("✅ SEND OK received");
-                        break;
+            Ok(n) => {
+                no_data_count = 0;
+
+                if !headers_done {
+                    if header_buf.extend_from_slice(&read_buf[..n]).is_err() {
+                        warn!("Response headers exceeded the {}-byte scan buffer", HEADER_SCAN_CAP);
+                        send_failed("Response headers too large").await;
+                        let _ = engine
+                            .send_timeout("AT+QICLOSE=0", Duration::from_secs(2))
+                            .await;
+                        return;
                     }
+                    if let Some(split) = find_header_end(&header_buf) {
+                        headers_done = true;
+                        UART_RESPONSE.send(FetchOutcome::Streaming).await;
+                        let body_so_far = &header_buf[split..];
+                        body_len += body_so_far.len();
+                        let _ = RESPONSE_BODY.write_all(body_so_far).await;
+                    }
+                    continue;
+                }
+
+                body_len += n;
+                let _ = RESPONSE_BODY.write_all(&read_buf[..n]).await;
+            }
+            Err(_) => {
+                no_data_count += 1;
+                if no_data_count > 6 {
+                    info!("✅ No more data");
+                    break;
                 }
             }
         }
-        Timer::after(Duration::from_millis(100)).await;
     }
 
-    if !got_send_ok {
-        warn!("SEND OK not received");
+    // Step 5: Close connection
+    info!("5. Closing connection...");
+    let _ = engine
+        .send_timeout("AT+QICLOSE=0", Duration::from_secs(2))
+        .await;
+
+    if headers_done {
+        info!("Total body streamed: {} bytes", body_len);
+        FETCH_DONE.signal(());
+    } else {
+        warn!("Response ended before headers completed");
+        send_failed("Incomplete response from origin").await;
+    }
+}
+
+/// Find the index just past the `\r\n\r\n` that ends an HTTP header block,
+/// if one is present yet.
+fn find_header_end(buf: &[u8]) -> Option<usize> {
+    buf.windows(4).position(|w| w == b"\r\n\r\n").map(|p| p + 4)
+}
+
+/// TLS counterpart to `fetch_via_lte`: opens an SSL context on the EC800K
+/// (`AT+QSSLCFG`/`QSSLOPEN`) instead of a plain `AT+QIOPEN` socket, for
+/// `https://` targets. Unlike the plain-TCP socket (opened in direct-push
+/// mode), SSL sockets on the EC800K don't push data unsolicited, so the
+/// response is collected by polling `AT+QSSLRECV` instead - and because
+/// `AT+QSSLRECV` comes back through the same line-oriented engine as every
+/// other command (losing the exact `\r\n` framing in the process - `mqtt`'s
+/// `try_read_packet` works around the equivalent problem for `AT+QIRD` by
+/// reading its payload raw instead of through the line framer, which this
+/// path doesn't do), there's no reliable byte offset to start streaming
+/// from partway through. So this path still
+/// buffers the (size-capped) response internally and hands the whole body
+/// to `RESPONSE_BODY` in one `write_all` once it's fully read, rather than
+/// `fetch_via_lte`'s read-by-read relay. Known follow-up, not done here:
+/// a `https://` response over 8 KiB is truncated (loudly - see the `warn!`
+/// below - but still truncated); removing that needs the engine itself to
+/// stop assuming line-oriented text for this path.
+async fn fetch_via_ssl(engine: &'static SharedAtEngine, host: &str, path: &str) {
+    info!("Fetching https://{}{} via LTE (TLS)...", host, path);
+    let mut engine = engine.lock().await;
+
+    // Configure SSL context 0: cipher suite auto-negotiated, SNI enabled so
+    // the peer's cert matches `host`, cert-chain checking off (no CA
+    // bundle provisioned on the device).
+    let _ = engine.send("AT+QSSLCFG=\"ciphersuite\",0,0XFFFF").await;
+    let _ = engine.send("AT+QSSLCFG=\"sni\",0,1").await;
+    let _ = engine.send("AT+QSSLCFG=\"seclevel\",0,0").await;
+
+    info!("1. Opening TLS connection...");
+    let mut open_cmd = String::<256>::new();
+    let _ = write!(open_cmd, "AT+QSSLOPEN=1,0,0,\"{}\",443,0", host);
+    if let Err(e) = engine.send_timeout(&open_cmd, Duration::from_secs(5)).await {
+        warn!("QSSLOPEN rejected: {:?}", e);
+        send_failed("TLS connection failed").await;
+        return;
     }
 
-    // Step 4: Collect HTTP response
-    info!("4. Collecting HTTP response...");
+    let open_result = match engine
+        .wait_for_prefix("+QSSLOPEN:", Duration::from_secs(30))
+        .await
+    {
+        Ok(line) => line,
+        Err(e) => {
+            warn!("QSSLOPEN result timed out: {:?}", e);
+            send_failed("TLS connection failed").await;
+            return;
+        }
+    };
+
+    if !open_result.contains("+QSSLOPEN: 0,0") {
+        warn!("TLS connection failed: {}", open_result.as_str());
+        send_failed("TLS connection failed").await;
+        return;
+    }
+
+    info!("✅ TLS connected");
+
+    let mut http_request = String::<512>::new();
+    let _ = write!(
+        http_request,
+        "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nUser-Agent: PicoLTE-Proxy/1.0\r\n\r\n",
+        path, host
+    );
+
+    info!("2. Sending HTTP request over TLS...");
+    let mut send_cmd = String::<64>::new();
+    let _ = write!(send_cmd, "AT+QSSLSEND=0,{}", http_request.len());
+    if let Err(e) = engine.send_timeout(&send_cmd, Duration::from_secs(5)).await {
+        warn!("No send prompt received: {:?}", e);
+        let _ = engine.send("AT+QSSLCLOSE=0").await;
+        send_failed("No send prompt").await;
+        return;
+    }
+
+    if let Err(e) = engine.write_raw(http_request.as_bytes()).await {
+        warn!("Failed to write HTTP request: {:?}", e);
+        let _ = engine.send("AT+QSSLCLOSE=0").await;
+        send_failed("Write failed").await;
+        return;
+    }
+
+    info!("3. Waiting for SEND OK...");
+    match engine.await_send_result(Duration::from_secs(5)).await {
+        Ok(()) => info!("✅ SEND OK received"),
+        Err(e) => warn!("SEND OK not received: {:?}", e),
+    }
+
+    // Step 4: poll for the response - QSSLRECV returns `+QSSLRECV: <len>`
+    // followed by up to <len> bytes of payload, or `+QSSLRECV: 0` when
+    // nothing is waiting yet.
+    info!("4. Collecting TLS response...");
     let mut http_data = String::<8192>::new();
-    let mut buffer = [0u8; 512];
     let mut no_data_count = 0;
+    let mut truncated = false;
 
     for _ in 0..60 {
-        // 30 seconds max
-        match embassy_time::with_timeout(Duration::from_millis(500), uart.read(&mut buffer)).await
+        match engine
+            .send_timeout("AT+QSSLRECV=0,1024", Duration::from_millis(500))
+            .await
         {
-            Ok(Ok(n)) => {
-                if let Ok(chunk) = core::str::from_utf8(&buffer[..n]) {
-                    let _ = http_data.push_str(chunk);
+            Ok(resp) => {
+                let mut got_any = false;
+                for line in resp.lines.iter() {
+                    if line.starts_with("+QSSLRECV:") {
+                        continue;
+                    }
+                    got_any = true;
+                    if http_data.push_str(line.as_str()).is_err() {
+                        truncated = true;
+                    }
+                }
+                if got_any {
                     no_data_count = 0;
-
-                    // Check if we have complete response
                     if http_data.contains("</html>") || http_data.contains("</HTML>") {
                         info!("✅ Complete response detected");
                         break;
                     }
+                } else {
+                    no_data_count += 1;
                 }
             }
-            _ => {
-                no_data_count += 1;
-                if no_data_count > 6 && http_data.len() > 0 {
-                    info!("✅ No more data");
-                    break;
+            Err(_) => no_data_count += 1,
+        }
+
+        if truncated {
+            // `fetch_via_ssl` has no byte-streaming path (see the doc
+            // comment above) - once the buffer is full there's nothing
+            // useful left to do but stop polling and send what we have.
+            // This still loses anything past 8 KiB; see the doc comment
+            // for why, and treat a full fix as follow-up work, not done here.
+            warn!("TLS response exceeded the 8 KiB buffer, truncating");
+            break;
+        }
+
+        if no_data_count > 6 && http_data.len() > 0 {
+            info!("✅ No more data");
+            break;
+        }
+    }
+
+    info!("Total response: {} bytes{}", http_data.len(), if truncated { " (truncated)" } else { "" });
+
+    info!("5. Closing connection...");
+    let _ = engine
+        .send_timeout("AT+QSSLCLOSE=0", Duration::from_secs(2))
+        .await;
+
+    if http_data.is_empty() {
+        send_failed("No data received over TLS").await;
+        return;
+    }
+
+    UART_RESPONSE.send(FetchOutcome::Streaming).await;
+    let body = extract_html(&http_data);
+    let _ = RESPONSE_BODY.write_all(body.as_bytes()).await;
+    FETCH_DONE.signal(());
+}
+
+/// Fetch `path` from `host:port` over the PPP stack, using an ordinary
+/// `embassy_net::tcp::TcpSocket` and real DNS instead of `AT+QIOPEN`. This
+/// is the PPP counterpart to `fetch_via_lte`, selected when
+/// [`USE_PPP_TRANSPORT`] is set.
+async fn fetch_via_ppp(
+    stack: &'static Stack<PppDevice<'static>>,
+    host: &str,
+    port: u16,
+    path: &str,
+) -> PppResponse {
+    info!("Fetching http://{}:{}{} via PPP...", host, port, path);
+
+    let addrs = match stack
+        .dns_query(host, embassy_net::dns::DnsQueryType::A)
+        .await
+    {
+        Ok(addrs) if !addrs.is_empty() => addrs,
+        Ok(_) => {
+            return PppResponse {
+                data: String::from("DNS lookup returned no addresses"),
+                success: false,
+            }
+        }
+        Err(_) => {
+            return PppResponse {
+                data: String::from("DNS lookup failed"),
+                success: false,
+            }
+        }
+    };
+
+    let mut rx_buffer = [0u8; 4096];
+    let mut tx_buffer = [0u8; 4096];
+    let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+    socket.set_timeout(Some(Duration::from_secs(15)));
+
+    if socket.connect((addrs[0], port)).await.is_err() {
+        return PppResponse {
+            data: String::from("TCP connect failed"),
+            success: false,
+        };
+    }
+
+    let mut http_request = String::<512>::new();
+    let _ = write!(
+        http_request,
+        "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nUser-Agent: PicoLTE-Proxy/1.0\r\n\r\n",
+        path, host
+    );
+
+    if socket.write_all(http_request.as_bytes()).await.is_err() {
+        return PppResponse {
+            data: String::from("Write failed"),
+            success: false,
+        };
+    }
+
+    let mut http_data = String::<8192>::new();
+    let mut buffer = [0u8; 512];
+    loop {
+        match socket.read(&mut buffer).await {
+            Ok(0) => break,
+            Ok(n) => {
+                if let Ok(chunk) = core::str::from_utf8(&buffer[..n]) {
+                    if http_data.push_str(chunk).is_err() {
+                        // Hit the fixed response cap; stop rather than
+                        // panicking (streaming responses is follow-up work).
+                        break;
+                    }
                 }
             }
+            Err(_) => break,
         }
     }
 
     info!("Total response: {} bytes", http_data.len());
+    socket.close();
 
-    // Step 5: Close connection
-    info!("5. Closing connection...");
-    let _ = uart.write_all(b"AT+QICLOSE=0\r\n").await;
-    Timer::after(Duration::from_millis(500)).await;
-
-    UartResponse {
+    PppResponse {
         data: http_data,
         success: true,
     }
 }
 
 #[embassy_executor::task]
-async fn http_server_task(stack: &'static Stack<cyw43::NetDriver<'static>>) {
+async fn http_server_task(
+    stack: &'static Stack<cyw43::NetDriver<'static>>,
+    ppp_stack: Option<&'static Stack<PppDevice<'static>>>,
+) {
     info!("HTTP server starting...");
     Timer::after(Duration::from_secs(1)).await;
 
@@ -383,11 +663,24 @@ async fn http_server_task(stack: &'static Stack<cyw43::NetDriver<'static>>) {
             request_str.split("\r\n").next().unwrap_or("")
         );
 
+        if request_str.starts_with("GET /gps") {
+            let status = gps::current().await;
+            let response = format_gps_response(&status);
+            if let Err(e) = socket.write_all(response.as_bytes()).await {
+                warn!("Write error: {:?}", e);
+            }
+            socket.close();
+            Timer::after(Duration::from_millis(100)).await;
+            continue;
+        }
+
         // Parse request
-        let (host, path) = if request_str.starts_with("GET /proxy?url=") {
-            // Parse URL parameter
-            if let Some(url_start) = request_str.find("url=http://") {
-                let url_part = &request_str[url_start + 11..];
+        let (scheme, host, path) = if request_str.starts_with("GET /proxy?url=") {
+            // Parse URL parameter; either scheme is accepted
+            let https_start = request_str.find("url=https://").map(|p| (Scheme::Https, p + 12));
+            let http_start = request_str.find("url=http://").map(|p| (Scheme::Http, p + 11));
+            if let Some((scheme, url_start)) = https_start.or(http_start) {
+                let url_part = &request_str[url_start..];
                 if let Some(url_end) = url_part.find(|c: char| c.is_whitespace() || c == '&') {
                     let full_url = &url_part[..url_end];
                     if let Some(slash_pos) = full_url.find('/') {
@@ -397,19 +690,19 @@ async fn http_server_task(stack: &'static Stack<cyw43::NetDriver<'static>>) {
                         let _ = host_str.push_str(h);
                         let mut path_str = String::<128>::new();
                         let _ = path_str.push_str(p);
-                        (Some(host_str), Some(path_str))
+                        (scheme, Some(host_str), Some(path_str))
                     } else {
                         let mut host_str = String::<64>::new();
                         let _ = host_str.push_str(full_url);
                         let mut path_str = String::<128>::new();
                         let _ = path_str.push_str("/");
-                        (Some(host_str), Some(path_str))
+                        (scheme, Some(host_str), Some(path_str))
                     }
                 } else {
-                    (None, None)
+                    (Scheme::Http, None, None)
                 }
             } else {
-                (None, None)
+                (Scheme::Http, None, None)
             }
         } else {
             // Default to www.gzxxzlk.com
@@ -417,44 +710,75 @@ async fn http_server_task(stack: &'static Stack<cyw43::NetDriver<'static>>) {
             let _ = host.push_str(DEFAULT_HOST);
             let mut path = String::<128>::new();
             let _ = path.push_str(DEFAULT_PATH);
-            (Some(host), Some(path))
+            (Scheme::Http, Some(host), Some(path))
         };
 
-        let response = if let (Some(h), Some(p)) = (host, path) {
+        if let (Some(h), Some(p)) = (host, path) {
             info!("Proxying: {}:{}", h.as_str(), p.as_str());
 
-            // Send request to UART task
-            UART_CHANNEL
-                .send(UartRequest {
-                    host: h.clone(),
-                    path: p.clone(),
-                })
-                .await;
-
-            // Wait for response
-            let uart_resp = UART_RESPONSE.receive().await;
-
-            if uart_resp.success {
-                // Extract HTML content
-                let html_content = extract_html(&uart_resp.data);
-
-                if html_content.len() > 0 {
-                    info!("✅ Sending {} bytes to browser", html_content.len());
-                    format_http_response(&html_content)
+            if let Some(ppp_stack) = ppp_stack {
+                // PPP still buffers the whole response (see `PppResponse`),
+                // so it replies the old-fashioned way: one write, headers
+                // included.
+                let response = if scheme == Scheme::Https {
+                    // PPP gives us a plain TCP stack; TLS-over-PPP needs an
+                    // embedded TLS client wired in on top of it, which is
+                    // separate follow-up work from the modem-SSL-context
+                    // path below.
+                    format_error_response("HTTPS over the PPP transport isn't implemented yet")
                 } else {
-                    info!("⚠️ No HTML content found");
-                    format_error_response("No HTML content found in response")
+                    let ppp_resp =
+                        fetch_via_ppp(ppp_stack, h.as_str(), scheme.default_port(), p.as_str())
+                            .await;
+                    if ppp_resp.success {
+                        let html_content = extract_html(&ppp_resp.data);
+                        if html_content.len() > 0 {
+                            info!("✅ Sending {} bytes to browser", html_content.len());
+                            format_http_response(&html_content)
+                        } else {
+                            info!("⚠️ No HTML content found");
+                            format_error_response("No HTML content found in response")
+                        }
+                    } else {
+                        format_error_response(ppp_resp.data.as_str())
+                    }
+                };
+                if let Err(e) = socket.write_all(response.as_bytes()).await {
+                    warn!("Write error: {:?}", e);
                 }
             } else {
-                format_error_response(uart_resp.data.as_str())
+                // Send request to the UART task and relay its streamed
+                // response straight through, chunk by chunk.
+                UART_CHANNEL
+                    .send(UartRequest {
+                        scheme,
+                        host: h.clone(),
+                        path: p.clone(),
+                    })
+                    .await;
+
+                match UART_RESPONSE.receive().await {
+                    FetchOutcome::Failed(msg) => {
+                        let response = format_error_response(msg.as_str());
+                        if let Err(e) = socket.write_all(response.as_bytes()).await {
+                            warn!("Write error: {:?}", e);
+                        }
+                    }
+                    FetchOutcome::Streaming => {
+                        const CHUNKED_HEADER: &str = "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n";
+                        if let Err(e) = socket.write_all(CHUNKED_HEADER.as_bytes()).await {
+                            warn!("Write error: {:?}", e);
+                        } else {
+                            relay_chunked_body(&mut socket).await;
+                        }
+                    }
+                }
             }
         } else {
-            format_error_response("Invalid URL format. Use /proxy?url=http://example.com")
-        };
-
-        // Send response
-        if let Err(e) = socket.write_all(response.as_bytes()).await {
-            warn!("Write error: {:?}", e);
+            let response = format_error_response("Invalid URL format. Use /proxy?url=http://example.com");
+            if let Err(e) = socket.write_all(response.as_bytes()).await {
+                warn!("Write error: {:?}", e);
+            }
         }
 
         socket.close();
@@ -462,6 +786,32 @@ async fn http_server_task(stack: &'static Stack<cyw43::NetDriver<'static>>) {
     }
 }
 
+/// Drain `RESPONSE_BODY` into `socket` as HTTP/1.1 chunks, bounded to
+/// `buf`'s size regardless of how large the upstream response is, until
+/// `FETCH_DONE` fires - then emit the terminating `0\r\n\r\n` chunk.
+async fn relay_chunked_body(socket: &mut TcpSocket<'_>) {
+    let mut buf = [0u8; 512];
+    loop {
+        match select(RESPONSE_BODY.read(&mut buf), FETCH_DONE.wait()).await {
+            Either::First(Ok(n)) => {
+                if n > 0 && write_chunk(socket, &buf[..n]).await.is_err() {
+                    return;
+                }
+            }
+            Either::First(Err(_)) | Either::Second(()) => break,
+        }
+    }
+    let _ = socket.write_all(b"0\r\n\r\n").await;
+}
+
+async fn write_chunk(socket: &mut TcpSocket<'_>, data: &[u8]) -> Result<(), ()> {
+    let mut size_line = String::<8>::new();
+    let _ = write!(size_line, "{:x}\r\n", data.len());
+    socket.write_all(size_line.as_bytes()).await.map_err(|_| ())?;
+    socket.write_all(data).await.map_err(|_| ())?;
+    socket.write_all(b"\r\n").await.map_err(|_| ())
+}
+
 fn extract_html(data: &str) -> String<8192> {
     let mut result = String::<8192>::new();
 
@@ -500,6 +850,39 @@ fn format_http_response(content: &str) -> String<8192> {
     response
 }
 
+fn format_gps_response(status: &gps::GpsStatus) -> String<512> {
+    let mut body = String::<256>::new();
+    match status {
+        gps::GpsStatus::Off => {
+            let _ = write!(body, "{{\"status\":\"off\"}}");
+        }
+        gps::GpsStatus::Pending => {
+            let _ = write!(body, "{{\"status\":\"pending\"}}");
+        }
+        gps::GpsStatus::Fix(fix) => {
+            let _ = write!(
+                body,
+                "{{\"status\":\"fix\",\"latitude\":{},\"longitude\":{},\"hdop\":{},\"altitude\":{},\"fix_quality\":{},\"utc_time\":\"{}\",\"satellites\":{}}}",
+                fix.latitude,
+                fix.longitude,
+                fix.hdop,
+                fix.altitude,
+                fix.fix_quality,
+                fix.utc_time.as_str(),
+                fix.satellites
+            );
+        }
+    }
+
+    let mut response = String::<512>::new();
+    let _ = write!(
+        response,
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nConnection: close\r\n\r\n{}",
+        body
+    );
+    response
+}
+
 fn format_error_response(error: &str) -> String<8192> {
     let mut response = String::<8192>::new();
     let _ = write!(
@@ -605,12 +988,57 @@ async fn main(spawner: Spawner) {
         uart_config,
     );
 
-    spawner.spawn(uart_task(uart)).unwrap();
-
     info!("UART initialized");
 
+    // Bring up the LTE transport: either the legacy single-socket
+    // AT+QIOPEN path, or PPP with a full embassy_net stack over it. Only
+    // the former gets `supervisor::supervisor_task` below - the PPP path
+    // has no automatic recovery yet if the link drops (see the comment at
+    // the end of `ppp_task`).
+    let ppp_stack = if USE_PPP_TRANSPORT {
+        let engine = AtEngine::new(uart);
+        let ppp_uart = ppp::dial(engine)
+            .await
+            .expect("PPP dial failed - check SIM/APN/coverage");
+
+        static PPP_STATE: StaticCell<PppState> = StaticCell::new();
+        let (ppp_device, ppp_runner) = embassy_net_ppp::new(PPP_STATE.init(PppState::new()));
+
+        spawner.spawn(ppp::ppp_task(ppp_runner, ppp_uart)).unwrap();
+
+        static PPP_RESOURCES: StaticCell<StackResources<3>> = StaticCell::new();
+        static PPP_STACK: StaticCell<Stack<PppDevice<'static>>> = StaticCell::new();
+        let ppp_stack = PPP_STACK.init(Stack::new(
+            ppp_device,
+            Config::default(),
+            PPP_RESOURCES.init(StackResources::new()),
+            embassy_rp::clocks::RoscRng,
+        ));
+        spawner.spawn(ppp_net_task(ppp_stack.run())).unwrap();
+
+        info!("PPP transport up, dialed via EC800K");
+        Some(&*ppp_stack)
+    } else {
+        static AT_ENGINE: StaticCell<SharedAtEngine> = StaticCell::new();
+        let engine = AT_ENGINE.init(Mutex::new(AtEngine::new(uart)));
+
+        // Idle high, pulsed low by the supervisor to force a reboot of a
+        // wedged modem; GP14 is otherwise unused on this board.
+        let modem_reset = Output::new(p.PIN_14, Level::High);
+
+        spawner
+            .spawn(supervisor::supervisor_task(engine, modem_reset))
+            .unwrap();
+        spawner.spawn(uart_task(engine)).unwrap();
+        if ENABLE_MQTT {
+            spawner.spawn(mqtt::mqtt_task(engine, MQTT_CONFIG)).unwrap();
+        }
+        spawner.spawn(gps::gnss_task(engine)).unwrap();
+        None
+    };
+
     // Start HTTP server
-    spawner.spawn(http_server_task(stack)).unwrap();
+    spawner.spawn(http_server_task(stack, ppp_stack)).unwrap();
 
     info!("==================================================");
     info!("🚀 Auto-Proxy Ready!");
@@ -618,6 +1046,7 @@ async fn main(spawner: Spawner) {
     info!("Open: http://192.168.4.1");
     info!("This will automatically show: {}", DEFAULT_HOST);
     info!("For other sites: http://192.168.4.1/proxy?url=http://example.com");
+    info!("GNSS fix: http://192.168.4.1/gps");
     info!("==================================================");
 
     // Keep LED blinking to show alive
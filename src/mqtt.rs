@@ -0,0 +1,483 @@
+//! MQTT publish/subscribe client over the LTE link.
+//!
+//! Built on the AT-command engine, this opens a second socket (link id 1,
+//! so it never collides with the HTTP proxy's transient link id 0) in
+//! buffered access mode and drives MQTT 3.1.1 CONNECT/PUBLISH/SUBSCRIBE/
+//! PINGREQ framing over it by hand. Incoming bytes are signalled by
+//! `+QIURC: "recv",1` on the shared URC channel and pulled with
+//! `AT+QIRD` rather than polled blindly.
+//!
+//! `AT+QIRD`'s header line (`+QIRD: <len>`) is read through the usual
+//! line-oriented AT engine, but the payload itself is pulled with
+//! `read_raw` straight into a byte buffer rather than through the line
+//! framer - MQTT packets are binary and routinely contain a bare `\r\n`
+//! (in the remaining-length encoding, packet identifiers over 255, or just
+//! payload bytes), and the framer splits lines on exactly that sequence.
+//! Running the payload through it would silently cut the packet at the
+//! first embedded `\r\n` instead of its real end. See `try_read_packet`.
+
+use core::fmt::Write as _;
+use defmt::{info, warn, Format};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::mutex::Mutex;
+use embassy_time::{Duration, Instant, Timer};
+use heapless::{String, Vec};
+
+use crate::at::{AtEngine, AtError, Urc, UrcSubscriber, URC_CHANNEL};
+
+/// Socket link id used for the persistent MQTT connection; link id 0 is
+/// reserved for the HTTP proxy's one-shot requests on the same modem.
+const MQTT_LINK_ID: u8 = 1;
+const MAX_TOPIC: usize = 64;
+const MAX_PAYLOAD: usize = 256;
+const OUTBOX_DEPTH: usize = 3;
+
+/// The AT engine shared between the HTTP proxy and this client - there is
+/// only one modem, so whoever needs it locks it for the duration of one
+/// request/response exchange.
+pub type SharedAtEngine = Mutex<CriticalSectionRawMutex, AtEngine>;
+
+#[derive(Clone)]
+pub struct OutgoingMessage {
+    pub topic: String<MAX_TOPIC>,
+    pub payload: Vec<u8, MAX_PAYLOAD>,
+    pub qos: u8,
+}
+
+pub struct IncomingMessage {
+    pub topic: String<MAX_TOPIC>,
+    pub payload: Vec<u8, MAX_PAYLOAD>,
+}
+
+/// Bounded depth-3 queue of outgoing publishes, so a slow/wedged modem
+/// conversation only holds up whatever is producing telemetry once three
+/// publishes are already waiting; `publish` then waits for a free slot.
+static OUTBOX: Channel<CriticalSectionRawMutex, OutgoingMessage, OUTBOX_DEPTH> = Channel::new();
+/// Delivered PUBLISHes from the broker, drained by subscribers.
+pub static INBOX: Channel<CriticalSectionRawMutex, IncomingMessage, 4> = Channel::new();
+
+#[derive(Debug, Format)]
+pub enum MqttError {
+    At(AtError),
+    /// CONNACK carried a non-zero return code, SUBACK carried the `0x80`
+    /// failure code, or `AT+QIOPEN` reported a failed connect (as `0`).
+    Rejected(u8),
+    /// A packet didn't fit the buffers this client uses, or didn't parse;
+    /// also used when an expected CONNACK/SUBACK never arrives and when the
+    /// broker closes the socket.
+    Malformed,
+}
+
+impl From<AtError> for MqttError {
+    fn from(e: AtError) -> Self {
+        MqttError::At(e)
+    }
+}
+
+pub struct MqttConfig {
+    pub host: &'static str,
+    pub port: u16,
+    pub client_id: &'static str,
+    pub username: Option<&'static str>,
+    pub password: Option<&'static str>,
+    pub keepalive_secs: u16,
+}
+
+/// Queue `payload` for publish on `topic`. Returns once the message is
+/// queued, not once it's actually on the wire - `mqtt_task` drains the
+/// outbox independently of whatever AT conversation happens to be running.
+pub async fn publish(topic: &str, payload: &[u8], qos: u8) -> Result<(), MqttError> {
+    let mut t = String::new();
+    t.push_str(topic).map_err(|_| MqttError::Malformed)?;
+    let mut p = Vec::new();
+    p.extend_from_slice(payload).map_err(|_| MqttError::Malformed)?;
+    OUTBOX
+        .send(OutgoingMessage {
+            topic: t,
+            payload: p,
+            qos,
+        })
+        .await;
+    Ok(())
+}
+
+/// Subscribe to `topic`; matching PUBLISHes show up on [`INBOX`]. Locks
+/// the shared engine for the round trip, same as any other AT exchange.
+pub async fn subscribe(engine: &'static SharedAtEngine, topic: &str, qos: u8) -> Result<(), MqttError> {
+    let mut var_payload: Vec<u8, 200> = Vec::new();
+    var_payload
+        .extend_from_slice(&1u16.to_be_bytes()) // packet identifier
+        .map_err(|_| MqttError::Malformed)?;
+    push_mqtt_string(&mut var_payload, topic)?;
+    var_payload.push(qos).map_err(|_| MqttError::Malformed)?;
+
+    let mut packet: Vec<u8, 256> = Vec::new();
+    packet.push(0x82).map_err(|_| MqttError::Malformed)?; // SUBSCRIBE
+    push_remaining_length(&mut packet, var_payload.len())?;
+    packet
+        .extend_from_slice(&var_payload)
+        .map_err(|_| MqttError::Malformed)?;
+
+    let mut eng = engine.lock().await;
+    send_mqtt_packet(&mut eng, &packet).await?;
+
+    for _ in 0..10 {
+        if let Some(ack) = try_read_packet(&mut eng).await? {
+            if ack.first() == Some(&0x90) {
+                // SUBACK return code `0x80` means the broker refused it.
+                let rc = *ack.get(4).unwrap_or(&0x80);
+                return if rc == 0x80 { Err(MqttError::Rejected(rc)) } else { Ok(()) };
+            }
+        }
+        Timer::after(Duration::from_millis(200)).await;
+    }
+    Err(MqttError::Malformed)
+}
+
+/// Own the MQTT connection for as long as the firmware runs: connect,
+/// drain the outbox, service keepalive, and forward inbound PUBLISHes to
+/// [`INBOX`]. Reconnects on any error.
+#[embassy_executor::task]
+pub async fn mqtt_task(engine: &'static SharedAtEngine, config: MqttConfig) {
+    let mut urcs = URC_CHANNEL
+        .subscriber()
+        .expect("mqtt_task is the only URC_CHANNEL subscriber taken at this slot");
+    loop {
+        match run_session(engine, &config, &mut urcs).await {
+            Ok(()) => info!("MQTT session ended"),
+            Err(e) => warn!("MQTT session failed: {:?}", e),
+        }
+        Timer::after(Duration::from_secs(5)).await;
+    }
+}
+
+async fn run_session(
+    engine: &'static SharedAtEngine,
+    config: &MqttConfig,
+    urcs: &mut UrcSubscriber,
+) -> Result<(), MqttError> {
+    {
+        let mut eng = engine.lock().await;
+        open_socket(&mut eng, config).await?;
+        mqtt_connect(&mut eng, config).await?;
+    }
+    info!("MQTT connected to {}:{}", config.host, config.port);
+
+    let mut last_ping = Instant::now();
+    let keepalive = Duration::from_secs(config.keepalive_secs as u64);
+
+    loop {
+        // One outgoing message per iteration, so incoming data and
+        // keepalive still get serviced even under a steady publish load.
+        if let Ok(msg) = OUTBOX.try_receive() {
+            let mut eng = engine.lock().await;
+            send_publish(&mut eng, &msg).await?;
+        }
+
+        if Instant::now() - last_ping > keepalive {
+            let mut eng = engine.lock().await;
+            send_pingreq(&mut eng).await?;
+            last_ping = Instant::now();
+        }
+
+        match embassy_time::with_timeout(Duration::from_millis(500), urcs.next_message_pure()).await {
+            Ok(Urc::IncomingData { link_id }) if link_id == MQTT_LINK_ID => {
+                let mut eng = engine.lock().await;
+                drain_incoming(&mut eng).await?;
+            }
+            Ok(Urc::Closed { link_id }) if link_id == MQTT_LINK_ID => {
+                warn!("MQTT socket closed by peer");
+                return Err(MqttError::Malformed);
+            }
+            _ => {}
+        }
+    }
+}
+
+async fn open_socket(engine: &mut AtEngine, config: &MqttConfig) -> Result<(), MqttError> {
+    let mut cmd = String::<256>::new();
+    let _ = write!(
+        cmd,
+        "AT+QIOPEN=1,{},\"TCP\",\"{}\",{},0,0",
+        MQTT_LINK_ID, config.host, config.port
+    );
+    engine.send_timeout(&cmd, Duration::from_secs(5)).await?;
+
+    let mut prefix = String::<16>::new();
+    let _ = write!(prefix, "+QIOPEN: {}", MQTT_LINK_ID);
+    let result = engine
+        .wait_for_prefix(&prefix, Duration::from_secs(30))
+        .await?;
+
+    let mut ok = String::<24>::new();
+    let _ = write!(ok, "+QIOPEN: {},0", MQTT_LINK_ID);
+    if !result.contains(ok.as_str()) {
+        return Err(MqttError::Rejected(0));
+    }
+    Ok(())
+}
+
+async fn mqtt_connect(engine: &mut AtEngine, config: &MqttConfig) -> Result<(), MqttError> {
+    let mut var_payload: Vec<u8, 200> = Vec::new();
+    push_mqtt_string(&mut var_payload, "MQTT")?;
+    var_payload.push(4).map_err(|_| MqttError::Malformed)?; // protocol level 4 = 3.1.1
+
+    let mut flags = 0x02u8; // clean session
+    if config.username.is_some() {
+        flags |= 0x80;
+    }
+    if config.password.is_some() {
+        flags |= 0x40;
+    }
+    var_payload.push(flags).map_err(|_| MqttError::Malformed)?;
+    var_payload
+        .extend_from_slice(&config.keepalive_secs.to_be_bytes())
+        .map_err(|_| MqttError::Malformed)?;
+
+    push_mqtt_string(&mut var_payload, config.client_id)?;
+    if let Some(u) = config.username {
+        push_mqtt_string(&mut var_payload, u)?;
+    }
+    if let Some(p) = config.password {
+        push_mqtt_string(&mut var_payload, p)?;
+    }
+
+    let mut packet: Vec<u8, 256> = Vec::new();
+    packet.push(0x10).map_err(|_| MqttError::Malformed)?; // CONNECT
+    push_remaining_length(&mut packet, var_payload.len())?;
+    packet
+        .extend_from_slice(&var_payload)
+        .map_err(|_| MqttError::Malformed)?;
+
+    send_mqtt_packet(engine, &packet).await?;
+
+    for _ in 0..10 {
+        if let Some(ack) = try_read_packet(engine).await? {
+            if ack.first() == Some(&0x20) {
+                let rc = *ack.get(3).unwrap_or(&0xFF);
+                return if rc == 0 { Ok(()) } else { Err(MqttError::Rejected(rc)) };
+            }
+        }
+        Timer::after(Duration::from_millis(200)).await;
+    }
+    Err(MqttError::Malformed)
+}
+
+async fn send_publish(engine: &mut AtEngine, msg: &OutgoingMessage) -> Result<(), MqttError> {
+    let mut var_payload: Vec<u8, 300> = Vec::new();
+    push_mqtt_string(&mut var_payload, msg.topic.as_str())?;
+    if msg.qos > 0 {
+        // A fixed packet identifier is fine here: this client never waits
+        // for PUBACK, matching the best-effort error handling the rest of
+        // this firmware uses for the modem link.
+        var_payload
+            .extend_from_slice(&1u16.to_be_bytes())
+            .map_err(|_| MqttError::Malformed)?;
+    }
+    var_payload
+        .extend_from_slice(&msg.payload)
+        .map_err(|_| MqttError::Malformed)?;
+
+    let mut packet: Vec<u8, 512> = Vec::new();
+    packet
+        .push(0x30 | (msg.qos << 1))
+        .map_err(|_| MqttError::Malformed)?;
+    push_remaining_length(&mut packet, var_payload.len())?;
+    packet
+        .extend_from_slice(&var_payload)
+        .map_err(|_| MqttError::Malformed)?;
+
+    send_mqtt_packet(engine, &packet).await
+}
+
+async fn send_pingreq(engine: &mut AtEngine) -> Result<(), MqttError> {
+    send_mqtt_packet(engine, &[0xC0, 0x00]).await
+}
+
+async fn drain_incoming(engine: &mut AtEngine) -> Result<(), MqttError> {
+    while let Some(packet) = try_read_packet(engine).await? {
+        if packet.first() == Some(&0xD0) {
+            continue; // PINGRESP
+        }
+        if packet.first().map(|b| b & 0xF0) == Some(0x30) {
+            if let Some(msg) = parse_publish(&packet) {
+                let _ = INBOX.try_send(msg);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Send `bytes` over the MQTT socket using the usual `QISEND` prompt
+/// handshake (same shape as `fetch_via_lte`'s HTTP request write).
+async fn send_mqtt_packet(engine: &mut AtEngine, bytes: &[u8]) -> Result<(), MqttError> {
+    let mut cmd = String::<32>::new();
+    let _ = write!(cmd, "AT+QISEND={},{}", MQTT_LINK_ID, bytes.len());
+    engine.send_timeout(&cmd, Duration::from_secs(5)).await?;
+    engine.write_raw(bytes).await?;
+    engine.await_send_result(Duration::from_secs(5)).await?;
+    Ok(())
+}
+
+/// Max bytes requested per `AT+QIRD` call - just a throttle on how much one
+/// round pulls, not a framer constraint, since the payload is now read raw
+/// rather than through the line framer (see `try_read_packet`).
+const QIRD_CHUNK: usize = 200;
+
+/// Max calls `try_read_packet` reassembles per packet before giving up and
+/// returning what it has - bounds how much a single pull can cost even if
+/// the peer keeps the link saturated.
+const MAX_QIRD_ROUNDS: u8 = 4;
+
+/// Timeout for each piece of a `QIRD` round: the header line, each raw read
+/// of the payload, and the trailing terminator.
+const QIRD_STEP_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Pull one pending packet, reassembling it from as many `AT+QIRD` calls as
+/// it takes to either drain the socket or fill `bytes`. Returns `None` once
+/// the socket has nothing left buffered.
+///
+/// The payload is pulled with `read_raw` rather than the usual
+/// `send_timeout`/line-framer path: MQTT packets are binary and routinely
+/// contain a bare `\r\n` (in the remaining-length encoding, a packet
+/// identifier over 255, or just the payload bytes themselves), and the line
+/// framer splits on exactly that sequence - reading them as text would
+/// silently cut the packet at the first embedded `\r\n` rather than at its
+/// real end, desyncing everything parsed after that point.
+async fn try_read_packet(engine: &mut AtEngine) -> Result<Option<Vec<u8, 256>>, MqttError> {
+    let mut bytes: Vec<u8, 256> = Vec::new();
+
+    for _ in 0..MAX_QIRD_ROUNDS {
+        let remaining = bytes.capacity() - bytes.len();
+        if remaining == 0 {
+            break;
+        }
+
+        let mut cmd = String::<32>::new();
+        let _ = write!(
+            cmd,
+            "AT+QIRD={},{}\r\n",
+            MQTT_LINK_ID,
+            QIRD_CHUNK.min(remaining)
+        );
+        engine.write_raw(cmd.as_bytes()).await?;
+
+        // The header line is plain text (`+QIRD: <len>`), so the line
+        // framer is fine for it - it's only the binary payload after it
+        // that isn't safe to run through `classify`.
+        let header = engine.wait_for_prefix("+QIRD: ", QIRD_STEP_TIMEOUT).await?;
+        let len: usize = header
+            .strip_prefix("+QIRD: ")
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(0);
+
+        if len == 0 {
+            engine.await_ok(QIRD_STEP_TIMEOUT).await?;
+            break;
+        }
+
+        // Read exactly `len` raw bytes regardless of how much of it fits
+        // in `bytes` - even once the reassembly cap is hit, the modem is
+        // still going to put that many bytes on the wire, and leaving them
+        // unread would desync the next command's response from this one's
+        // trailing `OK`.
+        let mut got = 0;
+        let mut overflowed = false;
+        while got < len {
+            let mut chunk = [0u8; 64];
+            let want = (len - got).min(chunk.len());
+            let n = engine.read_raw(&mut chunk[..want], QIRD_STEP_TIMEOUT).await?;
+            let take = n.min(bytes.capacity() - bytes.len());
+            let _ = bytes.extend_from_slice(&chunk[..take]);
+            overflowed |= take < n;
+            got += n;
+        }
+        if overflowed {
+            warn!(
+                "MQTT packet exceeded the {}-byte reassembly cap, truncating",
+                bytes.capacity()
+            );
+        }
+
+        engine.await_ok(QIRD_STEP_TIMEOUT).await?;
+    }
+
+    if bytes.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(bytes))
+    }
+}
+
+fn parse_publish(packet: &[u8]) -> Option<IncomingMessage> {
+    if packet.len() < 2 {
+        return None;
+    }
+    let qos = (packet[0] >> 1) & 0x03;
+    let (remaining_len, header_len) = decode_remaining_length(&packet[1..])?;
+    let mut idx = 1 + header_len;
+
+    if packet.len() < idx + 2 {
+        return None;
+    }
+    let topic_len = u16::from_be_bytes([packet[idx], packet[idx + 1]]) as usize;
+    idx += 2;
+    if packet.len() < idx + topic_len {
+        return None;
+    }
+    let topic_bytes = &packet[idx..idx + topic_len];
+    idx += topic_len;
+    if qos > 0 {
+        idx += 2; // packet identifier
+    }
+    let payload_end = packet.len().min(1 + header_len + remaining_len);
+    let payload_bytes = packet.get(idx..payload_end)?;
+
+    let mut topic = String::new();
+    topic.push_str(core::str::from_utf8(topic_bytes).ok()?).ok()?;
+    let mut payload = Vec::new();
+    let _ = payload.extend_from_slice(payload_bytes);
+    Some(IncomingMessage { topic, payload })
+}
+
+fn push_mqtt_string<const N: usize>(buf: &mut Vec<u8, N>, s: &str) -> Result<(), MqttError> {
+    let len = s.len() as u16;
+    buf.extend_from_slice(&len.to_be_bytes())
+        .map_err(|_| MqttError::Malformed)?;
+    buf.extend_from_slice(s.as_bytes())
+        .map_err(|_| MqttError::Malformed)
+}
+
+fn push_remaining_length<const N: usize>(buf: &mut Vec<u8, N>, mut len: usize) -> Result<(), MqttError> {
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        buf.push(byte).map_err(|_| MqttError::Malformed)?;
+        if len == 0 {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Decode an MQTT variable-length "remaining length" field. Returns the
+/// decoded value and how many bytes it occupied.
+fn decode_remaining_length(bytes: &[u8]) -> Option<(usize, usize)> {
+    let mut multiplier = 1usize;
+    let mut value = 0usize;
+    for (i, &b) in bytes.iter().enumerate() {
+        value += (b & 0x7F) as usize * multiplier;
+        if b & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+        multiplier *= 128;
+        if i >= 3 {
+            return None;
+        }
+    }
+    None
+}
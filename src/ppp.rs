@@ -0,0 +1,88 @@
+//! PPP data-mode transport.
+//!
+//! Dials the EC800K into PPP (`AT+QICSGP` to set the APN, then
+//! `ATD*99***1#`) and hands the UART to an `embassy-net-ppp` runner that
+//! negotiates LCP/IPCP and produces a second `embassy_net::Device`. That
+//! gives the proxy a standard `embassy_net::Stack` over the cellular link
+//! - ordinary `TcpSocket`s to arbitrary hosts, real DNS, streaming bodies
+//! of any size - instead of the single hand-rolled `AT+QIOPEN` session in
+//! `main.rs`.
+//!
+//! Selected at build time via [`USE_PPP_TRANSPORT`]; PPP and the legacy
+//! AT-socket path are mutually exclusive because PPP takes the UART over
+//! entirely once the link comes up.
+
+use defmt::{info, warn};
+use embassy_rp::peripherals::UART0;
+use embassy_rp::uart::BufferedUart;
+use embassy_time::{Duration, Timer};
+use heapless::String;
+
+use crate::at::{AtEngine, AtError};
+
+/// Flip this to switch the whole firmware from the legacy `AT+QIOPEN`
+/// single-socket transport to the PPP stack. There's no Cargo feature for
+/// this yet - a plain const is consistent with how `main.rs` configures
+/// itself (`WIFI_SSID`, `UART_BAUDRATE`, ...).
+pub const USE_PPP_TRANSPORT: bool = false;
+
+/// APN used for the PPP data session.
+const PPP_APN: &str = "CTNET";
+
+/// Number of receive and of transmit packet buffers in the
+/// `embassy-net-ppp` state.
+const PPP_STATE_SOCKETS: usize = 4;
+
+pub type PppDevice<'d> = embassy_net_ppp::Device<'d>;
+pub type PppState = embassy_net_ppp::State<PPP_STATE_SOCKETS, PPP_STATE_SOCKETS>;
+
+/// Dial the modem into PPP data mode and hand back the raw UART once
+/// `CONNECT` has been seen, so the caller can start the PPP runner on it.
+/// Consumes the `AtEngine` - once the link is in data mode there is no
+/// more AT command/response framing to be had until it's torn down.
+pub async fn dial(mut engine: AtEngine) -> Result<BufferedUart<'static, UART0>, AtError> {
+    let mut apn_cmd = String::<64>::new();
+    let _ = core::fmt::Write::write_fmt(
+        &mut apn_cmd,
+        format_args!("AT+QICSGP=1,1,\"{}\"", PPP_APN),
+    );
+    engine.send(&apn_cmd).await?;
+
+    info!("Dialing PPP data session (ATD*99***1#)...");
+    engine
+        .send_timeout("ATD*99***1#", Duration::from_secs(15))
+        .await?;
+
+    info!("PPP CONNECT received, handing UART to the PPP runner");
+    Ok(engine.into_uart())
+}
+
+/// Run LCP/IPCP negotiation and pump packets for the lifetime of the PPP
+/// link. Mirrors how `net_task` drives the cyw43 `Runner` - this task is
+/// spawned once and never returns under normal operation.
+#[embassy_executor::task]
+pub async fn ppp_task(
+    mut runner: embassy_net_ppp::Runner<'static>,
+    uart: BufferedUart<'static, UART0>,
+) -> ! {
+    let config = embassy_net_ppp::Config {
+        username: b"",
+        password: b"",
+    };
+
+    if let Err(e) = runner.run(uart, config, |_ipv4| {}).await {
+        warn!("PPP link terminated: {:?}", e);
+    }
+    // embassy-net-ppp's runner only returns when the link drops for good
+    // (it consumed the UART, so there's nothing left to redial with here).
+    // Unlike the legacy AT+QIOPEN path, `supervisor::supervisor_task` isn't
+    // spawned for PPP (see `main()`'s `USE_PPP_TRANSPORT` branch), so there
+    // is currently no automatic recovery here - a dropped PPP link is fatal
+    // to the LTE transport until the device is reset. Known follow-up, not
+    // done here: give the PPP path a redial loop of its own, or a
+    // supervisor-equivalent that can rebuild the whole UART/dial/runner
+    // chain.
+    loop {
+        Timer::after(Duration::from_secs(3600)).await;
+    }
+}
@@ -0,0 +1,201 @@
+//! Modem supervisor: turns the old one-shot init sequence (fixed
+//! `Timer::after` delays, no failure handling) into an explicit,
+//! restartable state machine, and watches for link-level trouble so the
+//! firmware can re-attach instead of getting stuck forever.
+//!
+//! States: `Reset -> AtReady -> SimReady -> Registered -> Attached ->
+//! ContextActive -> Ready`. Each step is retried a bounded number of times;
+//! if it still hasn't come up, the modem's reset/power-key line is pulsed
+//! and the whole sequence restarts from `Reset`. Once `Ready`, the
+//! supervisor watches the URC stream for `+QIURC: "closed"`/`"pdpdeact"`
+//! and for explicit re-attach requests from a wedged fetch, and restarts
+//! the sequence when either fires.
+
+use defmt::{info, warn, Format};
+use embassy_futures::select::{select, Either};
+use embassy_rp::gpio::Output;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Timer};
+
+use crate::at::{AtEngine, Response, Urc, UrcSubscriber, URC_CHANNEL};
+use crate::mqtt::SharedAtEngine;
+
+/// Attempts each init step gets before the whole sequence restarts from
+/// `Reset`.
+const RETRIES_PER_STEP: u8 = 3;
+/// How long the reset/power-key line is held low to force a fresh boot.
+const RESET_PULSE: Duration = Duration::from_millis(300);
+/// Settle time after releasing reset before the modem is ready for `AT`.
+const RESET_SETTLE: Duration = Duration::from_secs(3);
+
+#[derive(Clone, Copy, Debug, Format, PartialEq, Eq)]
+pub enum ModemState {
+    Reset,
+    AtReady,
+    SimReady,
+    Registered,
+    Attached,
+    ContextActive,
+    Ready,
+}
+
+/// Depth-1 "latest value" channel, same pattern as `gps::LATEST_FIX`: a
+/// reader always gets the current state back without consuming it.
+static MODEM_STATE: Channel<CriticalSectionRawMutex, ModemState, 1> = Channel::new();
+
+/// A fetch that hit a timeout it suspects is the modem wedging (rather
+/// than a one-off slow server) calls [`request_reattach`] to short-circuit
+/// the "wait for a `closed`/`pdpdeact` URC" path below.
+static REATTACH_REQUEST: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Current modem state, `Reset` if the supervisor hasn't published one yet.
+pub async fn current_state() -> ModemState {
+    match MODEM_STATE.try_receive() {
+        Ok(s) => {
+            let _ = MODEM_STATE.try_send(s);
+            s
+        }
+        Err(_) => ModemState::Reset,
+    }
+}
+
+/// Ask the supervisor to tear down and re-attach, e.g. because a fetch
+/// just timed out waiting on the modem.
+pub fn request_reattach() {
+    REATTACH_REQUEST.signal(());
+}
+
+async fn set_state(state: ModemState) {
+    let _ = MODEM_STATE.try_receive();
+    let _ = MODEM_STATE.try_send(state);
+}
+
+/// Bring the modem up and keep it up for as long as the firmware runs.
+#[embassy_executor::task]
+pub async fn supervisor_task(engine: &'static SharedAtEngine, mut reset_pin: Output<'static>) {
+    let mut urcs = URC_CHANNEL
+        .subscriber()
+        .expect("supervisor_task is the only URC_CHANNEL subscriber taken at this slot");
+
+    // Give the modem a moment to finish powering up before the first `AT`.
+    Timer::after(Duration::from_secs(2)).await;
+
+    loop {
+        set_state(ModemState::Reset).await;
+
+        if run_init_sequence(engine).await {
+            set_state(ModemState::Ready).await;
+            info!("Modem ready for traffic");
+            wait_for_trouble(&mut urcs).await;
+            warn!("Modem link trouble detected, re-attaching");
+        } else {
+            warn!("Modem init failed after retries, power-cycling");
+            pulse_reset(&mut reset_pin).await;
+        }
+    }
+}
+
+async fn pulse_reset(reset_pin: &mut Output<'static>) {
+    reset_pin.set_low();
+    Timer::after(RESET_PULSE).await;
+    reset_pin.set_high();
+    Timer::after(RESET_SETTLE).await;
+}
+
+/// Run the init sequence end to end, advancing `MODEM_STATE` as each step
+/// lands. Returns `false` (after `RETRIES_PER_STEP` failed attempts on
+/// some step) if the modem needs a power-cycle to make progress.
+async fn run_init_sequence(engine: &'static SharedAtEngine) -> bool {
+    let mut eng = engine.lock().await;
+
+    if !retry_step(&mut eng, "AT", |_| true).await {
+        return false;
+    }
+    set_state(ModemState::AtReady).await;
+
+    // "OK" alone just means the command was well-formed - a locked/missing
+    // SIM still answers `AT+CPIN?` with `OK`, so the actual unlock status
+    // has to come from the `+CPIN:` line itself.
+    if !retry_step(&mut eng, "AT+CPIN?", |r| r.contains("+CPIN: READY")).await {
+        return false;
+    }
+    set_state(ModemState::SimReady).await;
+
+    // Likewise `AT+CREG?` still terminates with `OK` while searching
+    // (`+CREG: 0,2`) or denied (`+CREG: 0,3`) - only `0,1`/`0,5` mean the
+    // modem actually landed on the home or a roaming network.
+    if !retry_step(&mut eng, "AT+CREG?", |r| {
+        r.contains("+CREG: 0,1") || r.contains("+CREG: 0,5")
+    })
+    .await
+    {
+        return false;
+    }
+    set_state(ModemState::Registered).await;
+
+    if !retry_step(&mut eng, "AT+CGATT=1", |_| true).await {
+        return false;
+    }
+    set_state(ModemState::Attached).await;
+
+    if !retry_step(&mut eng, "AT+QICSGP=1,1,\"CTNET\"", |_| true).await
+        || !retry_step(&mut eng, "AT+QIACT=1", |_| true).await
+    {
+        return false;
+    }
+    set_state(ModemState::ContextActive).await;
+
+    if !retry_step(
+        &mut eng,
+        "AT+QIDNSCFG=1,\"114.114.114.114\",\"8.8.8.8\"",
+        |_| true,
+    )
+    .await
+    {
+        return false;
+    }
+
+    true
+}
+
+/// Retry `cmd` up to `RETRIES_PER_STEP` times, treating it as successful
+/// only once it both returns `Ok` *and* `check` accepts the response -
+/// some commands (`AT+CPIN?`, `AT+CREG?`) still terminate with a plain
+/// `OK` even when the thing they're reporting on isn't actually ready.
+async fn retry_step(eng: &mut AtEngine, cmd: &str, check: impl Fn(&Response) -> bool) -> bool {
+    for attempt in 1..=RETRIES_PER_STEP {
+        match eng.send_timeout(cmd, Duration::from_secs(5)).await {
+            Ok(resp) if check(&resp) => return true,
+            Ok(resp) => warn!(
+                "{} attempt {}/{}: response not ready yet: {:?}",
+                cmd,
+                attempt,
+                RETRIES_PER_STEP,
+                resp.first()
+            ),
+            Err(e) => warn!(
+                "{} attempt {}/{} failed: {:?}",
+                cmd, attempt, RETRIES_PER_STEP, e
+            ),
+        }
+        Timer::after(Duration::from_millis(500)).await;
+    }
+    false
+}
+
+/// Wait until something suggests the data session is no longer usable:
+/// the HTTP proxy's link (id 0) closing or the PDP context deactivating
+/// out from under us, or an explicit re-attach request from a wedged
+/// fetch.
+async fn wait_for_trouble(urcs: &mut UrcSubscriber) {
+    loop {
+        match select(urcs.next_message_pure(), REATTACH_REQUEST.wait()).await {
+            Either::First(Urc::Closed { link_id: 0 }) => return,
+            Either::First(Urc::PdpDeactivated { .. }) => return,
+            Either::First(_) => continue,
+            Either::Second(()) => return,
+        }
+    }
+}